@@ -1,8 +1,77 @@
 use std::ffi::CString;
 
 use crate::ffi;
-use crate::{PyAny, PyResult, Python, PyNativeType, AsPyPointer};
-use crate::types::{PyBytes, PyTuple};
+use crate::exceptions::PyValueError;
+use crate::{PyAny, PyErr, PyResult, Python, PyNativeType, AsPyPointer};
+use crate::types::{PyBytes, PyDict, PyModule, PyString, PyTuple};
+use crate::Py;
+
+/// The `marshal` format version this build emits and expects to read back. A blob produced
+/// by `PyCodeObject::dumps` is only ever fed to `PyCodeObject::loads` within the same Python
+/// build, so a mismatch means the cache is stale or foreign rather than a different Python
+/// version we need to support.
+const MARSHAL_VERSION: u8 = ffi::Py_MARSHAL_VERSION as u8;
+
+/// The compilation mode accepted by [`PyCodeObject::compile_string_with_mode`].
+///
+/// This mirrors the `mode` argument of the Python builtin `compile()` and maps directly
+/// onto the `Py_file_input`/`Py_eval_input`/`Py_single_input` constants used by CPython's
+/// parser.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CompileMode {
+    /// Compile a module's worth of statements, as read from a file. This is the mode used
+    /// by [`PyCodeObject::compile_string`].
+    File,
+    /// Compile a single interactive statement, such as a line typed at the REPL.
+    Single,
+    /// Compile a single expression, which can later be run to produce a value.
+    Eval,
+}
+
+impl CompileMode {
+    fn as_raw(self) -> std::os::raw::c_int {
+        match self {
+            CompileMode::File => ffi::Py_file_input,
+            CompileMode::Single => ffi::Py_single_input,
+            CompileMode::Eval => ffi::Py_eval_input,
+        }
+    }
+}
+
+/// A single decoded bytecode instruction, as produced by [`PyCodeObject::disassemble`].
+#[derive(Debug)]
+pub struct Instruction {
+    /// The raw opcode byte, as stored in `co_code`.
+    pub opcode: u8,
+    /// The opcode's symbolic name, e.g. `"LOAD_CONST"`, resolved via the `opcode` module's
+    /// `opname` table.
+    pub name: String,
+    /// The instruction's argument, with any preceding `EXTENDED_ARG` prefixes folded in.
+    pub arg: u32,
+    /// The value `arg` refers to, if the opcode indexes into `co_consts`, `co_names` or
+    /// `co_varnames`; `None` for opcodes whose argument isn't a table index.
+    pub argval: Option<Py<PyAny>>,
+}
+
+/// The inputs accepted by [`PyCodeObject::new`].
+pub struct CodeObjectArgs<'a> {
+    pub argcount: i32,
+    pub posonlyargcount: i32,
+    pub kwonlyargcount: i32,
+    pub nlocals: i32,
+    pub stacksize: i32,
+    pub flags: i32,
+    pub code: &'a PyBytes,
+    pub consts: &'a PyTuple,
+    pub names: &'a PyTuple,
+    pub varnames: &'a PyTuple,
+    pub freevars: &'a PyTuple,
+    pub cellvars: &'a PyTuple,
+    pub filename: &'a str,
+    pub name: &'a str,
+    pub first_line_no: i32,
+    pub lnotab: &'a PyBytes,
+}
 
 /// Represents a Python code object
 #[repr(transparent)]
@@ -13,14 +82,156 @@ pyobject_native_type_core!(PyCodeObject, ffi::PyCode_Type, #checkfunction=ffi::P
 
 impl PyCodeObject {
 
+    /// Constructs a code object from its parts.
+    pub fn new<'p>(py: Python<'p>, args: CodeObjectArgs) -> PyResult<&'p PyCodeObject> {
+        let filename = CString::new(args.filename).unwrap();
+        let name = CString::new(args.name).unwrap();
+
+        unsafe {
+            let filename = py.from_owned_ptr_or_err::<PyAny>(ffi::PyUnicode_FromString(filename.as_ptr()))?;
+            let name = py.from_owned_ptr_or_err::<PyAny>(ffi::PyUnicode_FromString(name.as_ptr()))?;
+
+            #[cfg(Py_3_8)]
+            let ptr = ffi::PyCode_NewWithPosOnlyArgs(
+                args.argcount,
+                args.posonlyargcount,
+                args.kwonlyargcount,
+                args.nlocals,
+                args.stacksize,
+                args.flags,
+                args.code.as_ptr(),
+                args.consts.as_ptr(),
+                args.names.as_ptr(),
+                args.varnames.as_ptr(),
+                args.freevars.as_ptr(),
+                args.cellvars.as_ptr(),
+                filename.as_ptr(),
+                name.as_ptr(),
+                args.first_line_no,
+                args.lnotab.as_ptr(),
+            );
+
+            #[cfg(not(Py_3_8))]
+            let ptr = ffi::PyCode_New(
+                args.argcount,
+                args.kwonlyargcount,
+                args.nlocals,
+                args.stacksize,
+                args.flags,
+                args.code.as_ptr(),
+                args.consts.as_ptr(),
+                args.names.as_ptr(),
+                args.varnames.as_ptr(),
+                args.freevars.as_ptr(),
+                args.cellvars.as_ptr(),
+                filename.as_ptr(),
+                name.as_ptr(),
+                args.first_line_no,
+                args.lnotab.as_ptr(),
+            );
+
+            return py.from_owned_ptr_or_err::<PyCodeObject>(ptr as *mut ffi::PyObject);
+        }
+    }
+
     pub fn compile_string<'a>(py: Python<'a>, string: &str, filename: &str) -> PyResult<&'a PyCodeObject> {
+        Self::compile_string_with_mode(py, string, filename, CompileMode::File)
+    }
+
+    pub fn compile_string_with_mode<'a>(
+        py: Python<'a>,
+        string: &str,
+        filename: &str,
+        mode: CompileMode,
+    ) -> PyResult<&'a PyCodeObject> {
         let code = CString::new(string).unwrap();
         let filename = CString::new(filename).unwrap();
 
         unsafe {
             return py.from_borrowed_ptr_or_err::<PyCodeObject>(
-                ffi::Py_CompileString(code.as_ptr(), filename.as_ptr(), ffi::Py_file_input)
+                ffi::Py_CompileString(code.as_ptr(), filename.as_ptr(), mode.as_raw())
+            );
+        }
+    }
+
+    /// Executes this code object with the given global and local namespaces. Code compiled
+    /// with [`CompileMode::Eval`] returns the expression's value; code compiled with
+    /// [`CompileMode::File`] or [`CompileMode::Single`] returns `None`.
+    ///
+    /// If `globals` is `None`, a fresh dict seeded with `__builtins__` is used. If `locals`
+    /// is `None`, it defaults to the same namespace as `globals`, matching the behaviour of
+    /// the Python builtin `exec()`/`eval()`.
+    pub fn run(&self, globals: Option<&PyDict>, locals: Option<&PyDict>) -> PyResult<&PyAny> {
+        let py = self.py();
+
+        let globals = match globals {
+            Some(globals) => globals,
+            None => {
+                let globals = PyDict::new(py);
+                globals.set_item("__builtins__", unsafe {
+                    py.from_borrowed_ptr::<PyAny>(ffi::PyEval_GetBuiltins())
+                })?;
+                globals
+            }
+        };
+        let locals = locals.unwrap_or(globals);
+
+        unsafe {
+            return py.from_owned_ptr_or_err::<PyAny>(ffi::PyEval_EvalCode(
+                self.as_ptr(),
+                globals.as_ptr(),
+                locals.as_ptr(),
+            ));
+        }
+    }
+
+    /// Marshals this code object to a version-prefixed blob, readable back via [`PyCodeObject::loads`].
+    pub fn dumps(&self) -> PyResult<&PyBytes> {
+        let py = self.py();
+
+        unsafe {
+            let marshalled = py.from_owned_ptr_or_err::<PyBytes>(
+                ffi::PyMarshal_WriteObjectToString(self.as_ptr(), ffi::Py_MARSHAL_VERSION),
+            )?;
+
+            let mut data = Vec::with_capacity(marshalled.as_bytes().len() + 1);
+            data.push(MARSHAL_VERSION);
+            data.extend_from_slice(marshalled.as_bytes());
+
+            Ok(PyBytes::new(py, &data))
+        }
+    }
+
+    /// Deserializes a code object previously produced by [`PyCodeObject::dumps`].
+    pub fn loads<'a>(py: Python<'a>, data: &[u8]) -> PyResult<&'a PyCodeObject> {
+        let (version, marshalled) = data
+            .split_first()
+            .ok_or_else(|| PyValueError::new_err("cannot unmarshal an empty code object cache"))?;
+
+        if *version != MARSHAL_VERSION {
+            return Err(PyValueError::new_err(format!(
+                "marshal version mismatch: expected {}, got {}",
+                MARSHAL_VERSION, version
+            )));
+        }
+
+        unsafe {
+            let ptr = ffi::PyMarshal_ReadObjectFromString(
+                marshalled.as_ptr() as *const std::os::raw::c_char,
+                marshalled.len() as ffi::Py_ssize_t,
             );
+            if ptr.is_null() {
+                return Err(PyErr::fetch(py));
+            }
+
+            if ffi::PyCode_Check(ptr) == 0 {
+                ffi::Py_DECREF(ptr);
+                return Err(PyValueError::new_err(
+                    "unmarshalled data is not a code object",
+                ));
+            }
+
+            return Ok(py.from_owned_ptr::<PyCodeObject>(ptr));
         }
     }
 
@@ -66,13 +277,328 @@ impl PyCodeObject {
         }
     }
 
+    // `PyCodeObject`'s struct layout is not stable across CPython versions (3.11 alone
+    // reshuffled or dropped several of the fields below, e.g. `co_nlocals` is no longer a
+    // struct member and is instead computed on access). So, unlike a raw pointer cast, these
+    // go through attribute lookup, the same mechanism Python code uses to read `code.co_argcount`
+    // et al., which CPython keeps working across its own internal layout changes.
+
+    pub fn argcount(&self) -> PyResult<i32> {
+        self.getattr("co_argcount")?.extract()
+    }
+
+    pub fn kwonlyargcount(&self) -> PyResult<i32> {
+        self.getattr("co_kwonlyargcount")?.extract()
+    }
+
+    pub fn nlocals(&self) -> PyResult<i32> {
+        self.getattr("co_nlocals")?.extract()
+    }
+
+    pub fn stacksize(&self) -> PyResult<i32> {
+        self.getattr("co_stacksize")?.extract()
+    }
+
+    pub fn flags(&self) -> PyResult<i32> {
+        self.getattr("co_flags")?.extract()
+    }
+
+    pub fn first_line_no(&self) -> PyResult<i32> {
+        self.getattr("co_firstlineno")?.extract()
+    }
+
+    pub fn name(&self) -> PyResult<&PyString> {
+        Ok(self.getattr("co_name")?.downcast()?)
+    }
+
+    pub fn filename(&self) -> PyResult<&PyString> {
+        Ok(self.getattr("co_filename")?.downcast()?)
+    }
+
+    /// Decodes `co_code` into a sequence of [`Instruction`]s, resolving each opcode's
+    /// symbolic name and (where applicable) the constant, name or local variable its
+    /// argument refers to.
+    ///
+    /// `EXTENDED_ARG` prefixes are folded into the following instruction's `arg`, rather
+    /// than being surfaced as instructions in their own right, matching the behaviour of
+    /// Python's own `dis` module.
+    pub fn disassemble(&self) -> PyResult<Vec<Instruction>> {
+        let py = self.py();
+        let opcode_module = PyModule::import(py, "opcode")?;
+
+        let opnames = opcode_module.getattr("opname")?.extract::<Vec<String>>()?;
+        let hasconst = opcode_module.getattr("hasconst")?.extract::<Vec<u8>>()?;
+        let hasname = opcode_module.getattr("hasname")?.extract::<Vec<u8>>()?;
+        let haslocal = opcode_module.getattr("haslocal")?.extract::<Vec<u8>>()?;
+        let extended_arg = opcode_module.getattr("EXTENDED_ARG")?.extract::<u8>()?;
+
+        // Since Python 3.11, the specializing interpreter reserves `CACHE` code units
+        // directly after some opcodes (`LOAD_GLOBAL`, `CALL`, `BINARY_OP`, ...). These aren't
+        // real instructions, so skip over them rather than surfacing them as `CACHE` entries.
+        let inline_cache_entries = opcode_module
+            .getattr("_inline_cache_entries")
+            .ok()
+            .map(|obj| obj.extract::<Vec<u32>>())
+            .transpose()?;
+
+        let version_info = PyModule::import(py, "sys")?.getattr("version_info")?;
+        let version: (u8, u8) = (
+            version_info.getattr("major")?.extract()?,
+            version_info.getattr("minor")?.extract()?,
+        );
+
+        let consts = self.consts();
+        let names = self.names();
+        let var_names = self.var_names();
+        let code = self.code().as_bytes();
+
+        let mut instructions = Vec::new();
+        let mut pending_arg: u32 = 0;
+
+        let mut offset = 0;
+        while offset + 1 < code.len() {
+            let opcode = code[offset];
+            let arg = pending_arg << 8 | code[offset + 1] as u32;
+            offset += 2;
+
+            if opcode == extended_arg {
+                pending_arg = arg;
+                continue;
+            }
+            pending_arg = 0;
+
+            let name = opnames
+                .get(opcode as usize)
+                .cloned()
+                .unwrap_or_else(|| format!("<{}>", opcode));
+
+            let argval = if hasconst.contains(&opcode) {
+                Some(Py::from(consts.get_item(arg as usize)))
+            } else if hasname.contains(&opcode) {
+                // Some name opcodes keep flags in the low bits of their argument, above which
+                // sits the `co_names` index.
+                let name_index = match name.as_str() {
+                    "LOAD_GLOBAL" if version >= (3, 11) => arg >> 1,
+                    "LOAD_ATTR" if version >= (3, 12) => arg >> 1,
+                    "LOAD_SUPER_ATTR" => arg >> 2,
+                    _ => arg,
+                };
+                Some(Py::from(names.get_item(name_index as usize)))
+            } else if haslocal.contains(&opcode) {
+                Some(Py::from(var_names.get_item(arg as usize)))
+            } else {
+                None
+            };
+
+            instructions.push(Instruction { opcode, name, arg, argval });
+
+            if let Some(entries) = &inline_cache_entries {
+                let cache_units = *entries.get(opcode as usize).unwrap_or(&0);
+                offset += 2 * cache_units as usize;
+            }
+        }
+
+        Ok(instructions)
+    }
+
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::types::codeobject::PyCodeObject;
+    use crate::types::codeobject::{CodeObjectArgs, CompileMode, PyCodeObject, MARSHAL_VERSION};
+    use crate::types::{PyBytes, PyDict};
     use crate::{Python};
 
+    #[test]
+    fn test_run_eval() {
+        Python::with_gil(|py| {
+            let code_object = PyCodeObject::compile_string_with_mode(
+                py,
+                "1 + 2",
+                "<filename>",
+                CompileMode::Eval,
+            )
+            .expect("Code compilation failed");
+
+            let result = code_object.run(None, None).expect("Code execution failed");
+            let value: i32 = result.extract().expect("Result extraction failed");
+
+            assert_eq!(value, 3);
+        });
+    }
+
+    #[test]
+    fn test_run_single() {
+        Python::with_gil(|py| {
+            let code_object = PyCodeObject::compile_string_with_mode(
+                py,
+                "a = 3 + 6",
+                "<filename>",
+                CompileMode::Single,
+            )
+            .expect("Code compilation failed");
+
+            let globals = PyDict::new(py);
+            code_object.run(Some(globals), None).expect("Code execution failed");
+
+            let a: i32 = globals.get_item("a").expect("Expected `a` to be set").extract()
+                .expect("Expected `a` to be an int");
+            assert_eq!(a, 9);
+        });
+    }
+
+    #[test]
+    fn test_dumps_loads_roundtrip() {
+        Python::with_gil(|py| {
+            let code_object = PyCodeObject::compile_string(py, "a = 3 + 6", "<filename>")
+                .expect("Code compilation failed");
+
+            let marshalled = code_object.dumps().expect("Marshalling failed");
+            let restored = PyCodeObject::loads(py, marshalled.as_bytes())
+                .expect("Unmarshalling failed");
+
+            assert_eq!(
+                format!("{:?}", restored.consts()),
+                format!("{:?}", code_object.consts())
+            );
+        });
+    }
+
+    #[test]
+    fn test_loads_rejects_version_mismatch() {
+        Python::with_gil(|py| {
+            let data = [MARSHAL_VERSION.wrapping_add(1)];
+            let err = PyCodeObject::loads(py, &data).expect_err("Expected a version mismatch error");
+
+            assert!(err.to_string().contains("marshal version mismatch"));
+        });
+    }
+
+    #[test]
+    fn test_loads_rejects_non_code_object() {
+        Python::with_gil(|py| {
+            use crate::types::PyModule;
+
+            let marshal = PyModule::import(py, "marshal").expect("Failed to import marshal");
+            let marshalled: &PyBytes = marshal
+                .call_method1("dumps", ("not a code object",))
+                .expect("Marshalling failed")
+                .extract()
+                .expect("Expected bytes");
+
+            let mut data = vec![MARSHAL_VERSION];
+            data.extend_from_slice(marshalled.as_bytes());
+
+            let err = PyCodeObject::loads(py, &data).expect_err("Expected a value error");
+            assert!(err.to_string().contains("not a code object"));
+        });
+    }
+
+    #[test]
+    fn test_scalar_metadata() {
+        Python::with_gil(|py| {
+            let module = PyCodeObject::compile_string(py, "def f(a, b=1): return a + b", "<filename>")
+                .expect("Code compilation failed");
+
+            assert_eq!(module.filename().expect("filename() failed").to_string(), "<filename>");
+
+            let func_code = module
+                .consts()
+                .iter()
+                .find_map(|c| c.downcast::<PyCodeObject>().ok())
+                .expect("Expected f's code object among the module's consts");
+
+            assert_eq!(func_code.name().expect("name() failed").to_string(), "f");
+            assert_eq!(func_code.argcount().expect("argcount() failed"), 2);
+            assert_eq!(func_code.kwonlyargcount().expect("kwonlyargcount() failed"), 0);
+            assert_eq!(func_code.nlocals().expect("nlocals() failed"), 2);
+            assert_eq!(func_code.first_line_no().expect("first_line_no() failed"), 1);
+            assert!(func_code.stacksize().expect("stacksize() failed") > 0);
+            assert_ne!(func_code.flags().expect("flags() failed"), 0);
+        });
+    }
+
+    #[test]
+    fn test_disassemble() {
+        Python::with_gil(|py| {
+            let code_object = PyCodeObject::compile_string_with_mode(
+                py,
+                "1 + 2",
+                "<filename>",
+                CompileMode::Eval,
+            )
+            .expect("Code compilation failed");
+
+            let instructions = code_object.disassemble().expect("Disassembly failed");
+
+            assert!(!instructions.is_empty());
+            assert!(instructions.iter().any(|i| i.name == "LOAD_CONST" || i.name == "RETURN_VALUE"));
+        });
+    }
+
+    #[test]
+    fn test_disassemble_skips_inline_caches() {
+        Python::with_gil(|py| {
+            let module = PyCodeObject::compile_string(
+                py,
+                "def f(a, b):\n    return a.x + g(b)\n",
+                "<filename>",
+            )
+            .expect("Code compilation failed");
+
+            let func_code = module
+                .consts()
+                .iter()
+                .find_map(|c| c.downcast::<PyCodeObject>().ok())
+                .expect("Expected f's code object among the module's consts");
+
+            let instructions = func_code.disassemble().expect("Disassembly failed");
+
+            assert!(
+                !instructions.iter().any(|i| i.name == "CACHE"),
+                "disassemble() should skip CACHE filler units, not surface them as instructions"
+            );
+        });
+    }
+
+    #[test]
+    fn test_new_from_parts() {
+        Python::with_gil(|py| {
+            let template = PyCodeObject::compile_string(py, "a = 3 + 6", "<filename>")
+                .expect("Code compilation failed");
+
+            let code_object = PyCodeObject::new(py, CodeObjectArgs {
+                argcount: template.argcount().expect("argcount() failed"),
+                posonlyargcount: 0,
+                kwonlyargcount: template.kwonlyargcount().expect("kwonlyargcount() failed"),
+                nlocals: template.nlocals().expect("nlocals() failed"),
+                stacksize: template.stacksize().expect("stacksize() failed"),
+                flags: template.flags().expect("flags() failed"),
+                code: template.code(),
+                consts: template.consts(),
+                names: template.names(),
+                varnames: template.var_names(),
+                freevars: template.free_vars(),
+                cellvars: template.cell_vars(),
+                filename: "<synthesized>",
+                name: "<module>",
+                first_line_no: template.first_line_no().expect("first_line_no() failed"),
+                lnotab: PyBytes::new(py, &[]),
+            })
+            .expect("Code construction failed");
+
+            assert_eq!(code_object.filename().expect("filename() failed").to_string(), "<synthesized>");
+
+            code_object.run(None, None).expect("Code execution failed");
+
+            let globals = PyDict::new(py);
+            code_object.run(Some(globals), None).expect("Code execution failed");
+            let a: i32 = globals.get_item("a").expect("Expected `a` to be set").extract()
+                .expect("Expected `a` to be an int");
+            assert_eq!(a, 9);
+        });
+    }
+
     #[test]
     fn test_compile_string() {
         Python::with_gil(|py| {